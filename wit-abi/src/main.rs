@@ -3,7 +3,7 @@ use heck::*;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use wit_parser::*;
 
@@ -14,12 +14,42 @@ struct Options {
     #[structopt(long)]
     check: bool,
 
+    /// Generates a table of contents at the top of each `*.abi.md`. This is
+    /// already the default, so the flag has no further effect; it cannot be
+    /// combined with `--no-toc`.
+    #[structopt(long)]
+    toc: bool,
+
+    /// Disables generating a table of contents at the top of each
+    /// `*.abi.md`, which is otherwise included by default.
+    #[structopt(long, conflicts_with = "toc")]
+    no_toc: bool,
+
+    /// Renders every discovered `*.wit.md` into a single cross-linked
+    /// document at this path instead of one `*.abi.md` per file. Types
+    /// referenced from one interface that are defined in another link
+    /// correctly across the merged document.
+    #[structopt(long)]
+    merged_output: Option<PathBuf>,
+
     /// Files and/or directories to walk and look for `*.wit.md` files within.
     files: Vec<String>,
 }
 
+impl Options {
+    fn want_toc(&self) -> bool {
+        // `--toc` and `--no-toc` can't both be passed (see `conflicts_with`
+        // above), so `self.toc` never changes the result: the table of
+        // contents is generated unless `--no-toc` was passed.
+        self.toc || !self.no_toc
+    }
+}
+
 fn main() -> Result<()> {
     let options = Options::from_args();
+    if let Some(dst) = options.merged_output.clone() {
+        return options.render_merged(&dst);
+    }
     for arg in env::args().skip(1) {
         let path = Path::new(&arg);
         if path.is_dir() {
@@ -52,11 +82,8 @@ impl Options {
             Some(parent) => parent,
             None => return Ok(()),
         };
-        let filestem = match path.file_name().and_then(|s| s.to_str()) {
-            Some(name) => match name.strip_suffix(".wit.md") {
-                Some(name) => name,
-                None => return Ok(()),
-            },
+        let filestem = match wit_md_stem(path) {
+            Some(stem) => stem,
             None => return Ok(()),
         };
         let interface = Interface::parse_file(path)
@@ -68,34 +95,456 @@ impl Options {
             hrefs: HashMap::default(),
             funcs: 0,
             types: 0,
+            toc_entries: Vec::new(),
+            interface_name: None,
         };
-        markdown.process(&interface);
+        markdown.process(&interface, self.want_toc());
+        let rendered = resolve_hrefs(&markdown.src, &markdown.hrefs);
 
         let dst = dir.join(&format!("{}.abi.md", filestem));
         if self.check {
             let prev =
                 fs::read_to_string(&dst).with_context(|| format!("failed to read {:?}", dst))?;
-            if prev != markdown.src {
+            if prev != rendered {
+                bail!("not up to date: {}", dst.display());
+            }
+        } else {
+            fs::write(&dst, &rendered).with_context(|| format!("failed to write {:?}", dst))?;
+            println!("wrote {}", dst.display());
+        }
+        Ok(())
+    }
+
+    /// Recursively collects every `*.wit.md` file reachable from `path`,
+    /// paired with the interface name it will be rendered under (its file
+    /// stem, same as `render_file` uses for the `*.abi.md` name).
+    fn collect_wit_files(&self, path: &Path, out: &mut Vec<(String, PathBuf)>) -> Result<()> {
+        if path.is_dir() {
+            let cx = || format!("failed to read directory {:?}", path);
+            for dir in path.read_dir().with_context(&cx)? {
+                let dir = dir.with_context(&cx)?;
+                self.collect_wit_files(&dir.path(), out)?;
+            }
+        } else if let Some(stem) = wit_md_stem(path) {
+            out.push((stem.to_string(), path.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    /// Parses every `*.wit.md` reachable from `self.files` and renders them
+    /// all into a single document at `dst`, resolving doc-comment links
+    /// across interface boundaries.
+    fn render_merged(&self, dst: &Path) -> Result<()> {
+        let mut wit_files = Vec::new();
+        for arg in self.files.iter() {
+            self.collect_wit_files(Path::new(arg), &mut wit_files)?;
+        }
+        wit_files.sort();
+
+        let mut markdown = Markdown {
+            src: String::new(),
+            sizes: Default::default(),
+            hrefs: HashMap::default(),
+            funcs: 0,
+            types: 0,
+            toc_entries: Vec::new(),
+            interface_name: None,
+        };
+        for (name, path) in wit_files.iter() {
+            let interface = Interface::parse_file(path)
+                .with_context(|| format!("failed to parse input {:?}", path))?;
+            markdown.interface_name = Some(name.clone());
+            markdown.interface_preamble(name, &interface);
+            // Reset the per-section counters so each interface gets its own
+            // "# Types" / "# Functions" headings in the merged document.
+            markdown.types = 0;
+            markdown.funcs = 0;
+            markdown.process(&interface, false);
+        }
+        if self.want_toc() {
+            markdown.prepend_toc();
+        }
+        let rendered = resolve_hrefs(&markdown.src, &markdown.hrefs);
+
+        if self.check {
+            let prev =
+                fs::read_to_string(dst).with_context(|| format!("failed to read {:?}", dst))?;
+            if prev != rendered {
                 bail!("not up to date: {}", dst.display());
             }
         } else {
-            fs::write(&dst, &markdown.src).with_context(|| format!("failed to write {:?}", dst))?;
+            fs::write(dst, &rendered).with_context(|| format!("failed to write {:?}", dst))?;
             println!("wrote {}", dst.display());
         }
         Ok(())
     }
 }
 
+fn wit_md_stem(path: &Path) -> Option<&str> {
+    path.file_name()?.to_str()?.strip_suffix(".wit.md")
+}
+
+/// A core wasm value type, as produced by flattening a WIT type through the
+/// Canonical ABI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CoreType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl CoreType {
+    fn name(&self) -> &'static str {
+        match self {
+            CoreType::I32 => "i32",
+            CoreType::I64 => "i64",
+            CoreType::F32 => "f32",
+            CoreType::F64 => "f64",
+        }
+    }
+
+    /// Joins two core types together per the Canonical ABI's flattening
+    /// rules, used when multiple variant cases contribute a value at the
+    /// same flattened position.
+    fn join(a: CoreType, b: CoreType) -> CoreType {
+        use CoreType::*;
+        match (a, b) {
+            (a, b) if a == b => a,
+            (I32, F32) | (F32, I32) => I32,
+            _ => I64,
+        }
+    }
+}
+
+/// Joins the flattened representations of a type's cases (e.g. the arms of
+/// a `variant`, `option`, `expected`, or `union`) positionally, per the
+/// Canonical ABI: at each index, every case that has a value there
+/// contributes to the join, and cases that are shorter simply don't
+/// contribute past their own length.
+fn join_flattenings(cases: Vec<Vec<CoreType>>) -> Vec<CoreType> {
+    let max_len = cases.iter().map(|c| c.len()).max().unwrap_or(0);
+    let mut result = Vec::with_capacity(max_len);
+    for i in 0..max_len {
+        let mut cur = None;
+        for case in cases.iter() {
+            if let Some(ty) = case.get(i) {
+                cur = Some(match cur {
+                    None => *ty,
+                    Some(prev) => CoreType::join(prev, *ty),
+                });
+            }
+        }
+        result.push(cur.expect("max_len is derived from the longest case"));
+    }
+    result
+}
+
+/// Flattens a WIT type into the sequence of core wasm value types the
+/// Canonical ABI lowers it to.
+fn flatten_type(iface: &Interface, ty: &Type) -> Vec<CoreType> {
+    match ty {
+        Type::Unit => Vec::new(),
+        Type::Bool
+        | Type::U8
+        | Type::S8
+        | Type::U16
+        | Type::S16
+        | Type::U32
+        | Type::S32
+        | Type::Char => vec![CoreType::I32],
+        Type::U64 | Type::S64 => vec![CoreType::I64],
+        Type::Float32 => vec![CoreType::F32],
+        Type::Float64 => vec![CoreType::F64],
+        Type::String => vec![CoreType::I32, CoreType::I32],
+        Type::Handle(_) => vec![CoreType::I32],
+        Type::Id(id) => flatten_typedef(iface, &iface.types[*id].kind),
+    }
+}
+
+fn flatten_typedef(iface: &Interface, kind: &TypeDefKind) -> Vec<CoreType> {
+    match kind {
+        TypeDefKind::Type(t) => flatten_type(iface, t),
+        TypeDefKind::List(_) => vec![CoreType::I32, CoreType::I32],
+        TypeDefKind::Record(record) => record
+            .fields
+            .iter()
+            .flat_map(|f| flatten_type(iface, &f.ty))
+            .collect(),
+        TypeDefKind::Tuple(tuple) => tuple
+            .types
+            .iter()
+            .flat_map(|t| flatten_type(iface, t))
+            .collect(),
+        TypeDefKind::Flags(flags) => {
+            vec![CoreType::I32; ceil_divide(flags.flags.len(), 32)]
+        }
+        TypeDefKind::Option(t) => {
+            let mut result = vec![CoreType::I32];
+            result.extend(join_flattenings(vec![Vec::new(), flatten_type(iface, t)]));
+            result
+        }
+        TypeDefKind::Expected(Expected { ok, err }) => {
+            let mut result = vec![CoreType::I32];
+            result.extend(join_flattenings(vec![
+                flatten_type(iface, ok),
+                flatten_type(iface, err),
+            ]));
+            result
+        }
+        TypeDefKind::Variant(variant) => {
+            let mut result = vec![CoreType::I32];
+            result.extend(join_flattenings(
+                variant
+                    .cases
+                    .iter()
+                    .map(|case| flatten_type(iface, &case.ty))
+                    .collect(),
+            ));
+            result
+        }
+        TypeDefKind::Union(union) => {
+            let mut result = vec![CoreType::I32];
+            result.extend(join_flattenings(
+                union
+                    .cases
+                    .iter()
+                    .map(|case| flatten_type(iface, &case.ty))
+                    .collect(),
+            ));
+            result
+        }
+        TypeDefKind::Enum(_) => vec![CoreType::I32],
+        TypeDefKind::Future(_) => vec![CoreType::I32],
+        TypeDefKind::Stream(_) => vec![CoreType::I32],
+    }
+}
+
+fn ceil_divide(a: usize, b: usize) -> usize {
+    (a + b - 1) / b
+}
+
+/// The flattened core wasm signature of a function's params or results,
+/// both before and after the Canonical ABI's "too many flattened values"
+/// indirection kicks in.
+struct CoreSignature {
+    direct: Vec<CoreType>,
+    indirect: Vec<CoreType>,
+}
+
+fn flatten_params(iface: &Interface, params: &[(String, Type)]) -> CoreSignature {
+    let direct: Vec<_> = params
+        .iter()
+        .flat_map(|(_, ty)| flatten_type(iface, ty))
+        .collect();
+    let indirect = if direct.len() > MAX_FLAT_PARAMS {
+        vec![CoreType::I32]
+    } else {
+        direct.clone()
+    };
+    CoreSignature { direct, indirect }
+}
+
+fn flatten_result(iface: &Interface, ty: &Type) -> CoreSignature {
+    let direct = flatten_type(iface, ty);
+    let indirect = if direct.len() > MAX_FLAT_RESULTS {
+        vec![CoreType::I32]
+    } else {
+        direct.clone()
+    };
+    CoreSignature { direct, indirect }
+}
+
+const MAX_FLAT_PARAMS: usize = 16;
+const MAX_FLAT_RESULTS: usize = 1;
+
+fn print_core_sig(types: &[CoreType]) -> String {
+    format!(
+        "({})",
+        types
+            .iter()
+            .map(|t| t.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Markers `docs()` wraps doc-comment prose in so `resolve_hrefs` can find
+/// it later without mistaking other generated markdown for prose. These are
+/// control characters that never appear in parsed WIT doc comments, and are
+/// stripped out before the final output is written.
+const DOCS_START: &str = "\u{2}";
+const DOCS_END: &str = "\u{3}";
+
+/// Separates the owning interface name (namespaced `hrefs` keys use
+/// `"{interface}.{name}"` in `--merged-output` mode) from the doc-comment
+/// prose within a `DOCS_START`/`DOCS_END` block. Empty when rendering a
+/// single `*.wit.md` on its own.
+const DOCS_IFACE_SEP: &str = "\u{4}";
+
+/// Scans the doc-comment prose emitted by `docs()` for backtick-quoted
+/// identifiers (e.g. `` `some-type` `` or `` `type::field` ``) and rewrites
+/// any that name an entry in `hrefs` into a markdown link pointing at its
+/// anchor. Code fences within doc comments are left untouched.
+fn resolve_hrefs(src: &str, hrefs: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    while let Some(start) = rest.find(DOCS_START) {
+        out.push_str(&rest[..start]);
+        let after_start = &rest[start + DOCS_START.len()..];
+        let end = after_start
+            .find(DOCS_END)
+            .expect("docs() always closes a DOCS_START with a DOCS_END");
+        let block = &after_start[..end];
+        let sep = block
+            .find(DOCS_IFACE_SEP)
+            .expect("docs() always separates the interface name with DOCS_IFACE_SEP");
+        let iface = &block[..sep];
+        let iface = if iface.is_empty() { None } else { Some(iface) };
+        let content = &block[sep + DOCS_IFACE_SEP.len()..];
+        out.push_str(&link_doc_block(content, hrefs, iface));
+        rest = &after_start[end + DOCS_END.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn link_doc_block(block: &str, hrefs: &HashMap<String, String>, iface: Option<&str>) -> String {
+    let mut out = String::with_capacity(block.len());
+    let mut in_fence = false;
+    for line in block.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            out.push_str(line);
+        } else if in_fence {
+            out.push_str(line);
+        } else {
+            link_backtick_idents(line, hrefs, iface, &mut out);
+        }
+    }
+    out
+}
+
+fn link_backtick_idents(
+    line: &str,
+    hrefs: &HashMap<String, String>,
+    iface: Option<&str>,
+    out: &mut String,
+) {
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        out.push_str(&rest[..start]);
+        let after_start = &rest[start + 1..];
+        match after_start.find('`') {
+            Some(end) => {
+                let ident = &after_start[..end];
+                // Doc comments name same-interface types and functions the
+                // same bare way the source `.wit.md` did; only fall back to
+                // the interface-qualified key (used by `--merged-output` to
+                // keep cross-interface names apart) when the bare one isn't
+                // in `hrefs`.
+                let href = hrefs
+                    .get(ident)
+                    .or_else(|| iface.and_then(|iface| hrefs.get(&format!("{}.{}", iface, ident))));
+                match href {
+                    Some(href) => out.push_str(&format!("[`{}`]({})", ident, href)),
+                    None => out.push_str(&format!("`{}`", ident)),
+                }
+                rest = &after_start[end + 1..];
+            }
+            None => {
+                out.push('`');
+                rest = after_start;
+            }
+        }
+    }
+    out.push_str(rest);
+}
+
 pub struct Markdown {
     src: String,
     sizes: SizeAlign,
     hrefs: HashMap<String, String>,
     funcs: usize,
     types: usize,
+    /// `(kind, name, anchor)` for every type and function, in the order
+    /// they're rendered, used to build the table of contents.
+    toc_entries: Vec<(&'static str, String, String)>,
+    /// Set while rendering a merged, multi-interface document so anchors
+    /// and `hrefs` keys are namespaced per-interface and don't collide
+    /// across interfaces that happen to share a type or function name.
+    /// `None` when rendering a single `*.wit.md` on its own.
+    interface_name: Option<String>,
 }
 
 impl Markdown {
-    fn process(&mut self, iface: &Interface) {
+    /// Namespaces `name` into an anchor id unique across a merged document.
+    fn anchor_id(&self, name: &str) -> String {
+        match &self.interface_name {
+            Some(iface) => format!("{}-{}", iface.to_snake_case(), name.to_snake_case()),
+            None => name.to_snake_case(),
+        }
+    }
+
+    /// Namespaces `name` into an `hrefs` key unique across a merged
+    /// document, using the same `interface.name` syntax a `*.wit.md` file
+    /// would use to refer to an imported type.
+    fn href_key(&self, name: &str) -> String {
+        match &self.interface_name {
+            Some(iface) => format!("{}.{}", iface, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Qualifies `name` with the owning interface for display in the table
+    /// of contents, so two interfaces with a same-named type or function
+    /// don't show up as identical, indistinguishable rows.
+    fn toc_label(&self, name: &str) -> String {
+        match &self.interface_name {
+            Some(iface) => format!("{}.{}", iface, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Emits an interface-level heading ahead of a merged document's
+    /// section for `iface`.
+    fn interface_preamble(&mut self, name: &str, iface: &Interface) {
+        self.src.push_str(&format!("# Interface `{}`\n\n", name));
+
+        // Resources this interface itself declares. These aren't pulled in
+        // from another interface in the merged set, so they're listed
+        // under their own heading rather than as "imports".
+        self.src.push_str("### Resources\n\n");
+        let resources: Vec<_> = iface.resources.iter().map(|(_, r)| &r.name).collect();
+        if resources.is_empty() {
+            self.src.push_str("_None._\n\n");
+        } else {
+            for resource in resources {
+                self.src.push_str(&format!("- `{}`\n", resource));
+            }
+            self.src.push_str("\n");
+        }
+
+        self.src.push_str("### Exports\n\n");
+        let named_types: Vec<_> = iface
+            .types
+            .iter()
+            .filter_map(|(_, ty)| ty.name.as_deref())
+            .collect();
+        if named_types.is_empty() && iface.functions.is_empty() {
+            self.src.push_str("_None._\n\n");
+        } else {
+            for name in named_types {
+                self.src.push_str(&format!("- type `{}`\n", name));
+            }
+            for func in iface.functions.iter() {
+                self.src.push_str(&format!("- function `{}`\n", func.name));
+            }
+            self.src.push_str("\n");
+        }
+    }
+
+    fn process(&mut self, iface: &Interface, toc: bool) {
         self.sizes.fill(iface);
 
         for (id, ty) in iface.types.iter() {
@@ -129,13 +578,17 @@ impl Markdown {
             }
             self.funcs += 1;
 
+            let func_anchor_id = self.anchor_id(&func.name);
+
             self.src.push_str("----\n\n");
             self.src.push_str(&format!(
                 "#### <a href=\"#{0}\" name=\"{0}\"></a> `",
-                func.name.to_snake_case()
+                func_anchor_id,
             ));
-            self.hrefs
-                .insert(func.name.clone(), format!("#{}", func.name.to_snake_case()));
+            let href = format!("#{}", func_anchor_id);
+            self.toc_entries
+                .push(("Functions", self.toc_label(&func.name), href.clone()));
+            self.hrefs.insert(self.href_key(&func.name), href);
             self.src.push_str(&func.name);
             self.src.push_str("` ");
             self.src.push_str("\n\n");
@@ -147,7 +600,7 @@ impl Markdown {
                     self.src.push_str(&format!(
                         "- <a href=\"#{f}.{p}\" name=\"{f}.{p}\"></a> `{}`: ",
                         name,
-                        f = func.name.to_snake_case(),
+                        f = func_anchor_id,
                         p = name.to_snake_case(),
                     ));
                     self.print_ty(iface, ty, false);
@@ -160,9 +613,81 @@ impl Markdown {
             self.src.push_str("\n");
 
             self.src.push_str("\n");
+            self.func_core_abi(iface, func);
+        }
+
+        if toc {
+            self.prepend_toc();
         }
     }
 
+    /// Builds a nested table of contents linking to every type and function
+    /// anchor, grouped by kind, and prepends it to `self.src`.
+    fn prepend_toc(&mut self) {
+        if self.toc_entries.is_empty() {
+            return;
+        }
+
+        const KIND_ORDER: &[&str] = &[
+            "Records",
+            "Tuples",
+            "Flags",
+            "Variants",
+            "Enums",
+            "Unions",
+            "Options",
+            "Expecteds",
+            "Futures",
+            "Streams",
+            "Type Aliases",
+            "Functions",
+        ];
+
+        let mut toc = String::new();
+        toc.push_str("# Table of Contents\n\n");
+        for kind in KIND_ORDER {
+            let entries: Vec<_> = self
+                .toc_entries
+                .iter()
+                .filter(|(k, _, _)| k == kind)
+                .collect();
+            if entries.is_empty() {
+                continue;
+            }
+            toc.push_str(&format!("- {}\n", kind));
+            for (_, name, href) in entries {
+                toc.push_str(&format!("  - [`{}`]({})\n", name, href));
+            }
+        }
+        toc.push_str("\n");
+
+        self.src = toc + &self.src;
+    }
+
+    /// Renders the flattened core wasm signature the Canonical ABI produces
+    /// for this function, both in its "direct" form (every lowered value
+    /// passed as its own param/result) and its "indirect" form (the form
+    /// actually used once the direct signature is too large to pass
+    /// directly, per the Canonical ABI's flattening rules).
+    fn func_core_abi(&mut self, iface: &Interface, func: &Function) {
+        self.src.push_str("##### Core ABI\n\n");
+
+        let params = flatten_params(iface, &func.params);
+        let results = flatten_result(iface, &func.result);
+
+        self.src.push_str(&format!(
+            "- direct: `{}` -> `{}`\n",
+            print_core_sig(&params.direct),
+            print_core_sig(&results.direct),
+        ));
+        self.src.push_str(&format!(
+            "- indirect: `{}` -> `{}`\n",
+            print_core_sig(&params.indirect),
+            print_core_sig(&results.indirect),
+        ));
+        self.src.push_str("\n");
+    }
+
     fn type_record(
         &mut self,
         iface: &Interface,
@@ -171,20 +696,22 @@ impl Markdown {
         record: &Record,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Records");
         self.src.push_str("record\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Record Fields\n\n");
         for field in record.fields.iter() {
+            let r = self.anchor_id(name);
+            let f = field.name.to_snake_case();
             self.src.push_str(&format!(
                 "- <a href=\"{r}.{f}\" name=\"{r}.{f}\"></a> [`{name}`](#{r}.{f}): ",
-                r = name.to_snake_case(),
-                f = field.name.to_snake_case(),
+                r = r,
+                f = f,
                 name = field.name,
             ));
             self.hrefs.insert(
-                format!("{}::{}", name, field.name),
-                format!("#{}.{}", name.to_snake_case(), field.name.to_snake_case()),
+                self.href_key(&format!("{}::{}", name, field.name)),
+                format!("#{}.{}", r, f),
             );
             self.print_ty(iface, &field.ty, false);
             self.src.push_str("\n\n");
@@ -201,9 +728,9 @@ impl Markdown {
         tuple: &Tuple,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Tuples");
         self.src.push_str("tuple\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Tuple Types\n\n");
         for field in tuple.types.iter() {
             self.src.push_str(&format!("- ",));
@@ -214,26 +741,28 @@ impl Markdown {
 
     fn type_flags(
         &mut self,
-        _iface: &Interface,
+        iface: &Interface,
         id: TypeId,
         name: &str,
         record: &Flags,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Flags");
         self.src.push_str("flags\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Flags Fields\n\n");
         for (i, field) in record.flags.iter().enumerate() {
+            let r = self.anchor_id(name);
+            let f = field.name.to_snake_case();
             self.src.push_str(&format!(
                 "- <a href=\"{r}.{f}\" name=\"{r}.{f}\"></a> [`{name}`](#{r}.{f})",
-                r = name.to_snake_case(),
-                f = field.name.to_snake_case(),
+                r = r,
+                f = f,
                 name = field.name,
             ));
             self.hrefs.insert(
-                format!("{}::{}", name, field.name),
-                format!("#{}.{}", name.to_snake_case(), field.name.to_snake_case()),
+                self.href_key(&format!("{}::{}", name, field.name)),
+                format!("#{}.{}", r, f),
             );
             self.src.push_str("\n\n");
             self.docs(&field.docs);
@@ -250,20 +779,22 @@ impl Markdown {
         variant: &Variant,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Variants");
         self.src.push_str("variant\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Variant Cases\n\n");
         for case in variant.cases.iter() {
+            let v = self.anchor_id(name);
+            let c = case.name.to_snake_case();
             self.src.push_str(&format!(
                 "- <a href=\"{v}.{c}\" name=\"{v}.{c}\"></a> [`{name}`](#{v}.{c})",
-                v = name.to_snake_case(),
-                c = case.name.to_snake_case(),
+                v = v,
+                c = c,
                 name = case.name,
             ));
             self.hrefs.insert(
-                format!("{}::{}", name, case.name),
-                format!("#{}.{}", name.to_snake_case(), case.name.to_snake_case()),
+                self.href_key(&format!("{}::{}", name, case.name)),
+                format!("#{}.{}", v, c),
             );
             self.src.push_str(": ");
             self.print_ty(iface, &case.ty, false);
@@ -273,21 +804,23 @@ impl Markdown {
         }
     }
 
-    fn type_enum(&mut self, _iface: &Interface, id: TypeId, name: &str, enum_: &Enum, docs: &Docs) {
-        self.print_type_header(name);
+    fn type_enum(&mut self, iface: &Interface, id: TypeId, name: &str, enum_: &Enum, docs: &Docs) {
+        self.print_type_header(name, "Enums");
         self.src.push_str("enum\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Enum Cases\n\n");
         for case in enum_.cases.iter() {
+            let v = self.anchor_id(name);
+            let c = case.name.to_snake_case();
             self.src.push_str(&format!(
                 "- <a href=\"{v}.{c}\" name=\"{v}.{c}\"></a> [`{name}`](#{v}.{c})",
-                v = name.to_snake_case(),
-                c = case.name.to_snake_case(),
+                v = v,
+                c = c,
                 name = case.name,
             ));
             self.hrefs.insert(
-                format!("{}::{}", name, case.name),
-                format!("#{}.{}", name.to_snake_case(), case.name.to_snake_case()),
+                self.href_key(&format!("{}::{}", name, case.name)),
+                format!("#{}.{}", v, c),
             );
             self.src.push_str("\n\n");
             self.docs(&case.docs);
@@ -303,9 +836,9 @@ impl Markdown {
         union: &Union,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Unions");
         self.src.push_str("union\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Union Cases\n\n");
         for case in union.cases.iter() {
             self.src.push_str(&format!("- ",));
@@ -324,9 +857,9 @@ impl Markdown {
         type_: &Type,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Options");
         self.src.push_str("option\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Option\n\n");
         self.src.push_str(&format!("- ",));
         self.print_ty(iface, &type_, false);
@@ -341,9 +874,9 @@ impl Markdown {
         expected: &Expected,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Expecteds");
         self.src.push_str("expected\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Expected\n\n");
         self.src.push_str(&format!("- ok: ",));
         self.print_ty(iface, &expected.ok, false);
@@ -361,9 +894,9 @@ impl Markdown {
         type_: &Type,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Futures");
         self.src.push_str("future\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Future\n\n");
         self.src.push_str(&format!("- ",));
         self.print_ty(iface, &type_, false);
@@ -378,9 +911,9 @@ impl Markdown {
         stream: &Stream,
         docs: &Docs,
     ) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Streams");
         self.src.push_str("stream\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n### Stream\n\n");
         self.src.push_str(&format!("- ok: ",));
         self.print_ty(iface, &stream.element, false);
@@ -391,10 +924,10 @@ impl Markdown {
     }
 
     fn type_alias(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
-        self.print_type_header(name);
+        self.print_type_header(name, "Type Aliases");
         self.print_ty(iface, ty, true);
         self.src.push_str("\n\n");
-        self.print_type_info(id, docs);
+        self.print_type_info(iface, id, docs);
         self.src.push_str("\n");
     }
 
@@ -423,10 +956,11 @@ impl Markdown {
                 let ty = &iface.types[*id];
                 if !skip_name {
                     if let Some(name) = &ty.name {
+                        let anchor_id = self.anchor_id(name);
                         self.src.push_str("[`");
                         self.src.push_str(name);
                         self.src.push_str("`](#");
-                        self.src.push_str(&name.to_snake_case());
+                        self.src.push_str(&anchor_id);
                         self.src.push_str(")");
                         return;
                     }
@@ -455,8 +989,23 @@ impl Markdown {
                         self.print_ty(iface, err, false);
                         self.src.push_str(">");
                     }
-                    TypeDefKind::Variant(_v) => {
-                        unreachable!()
+                    TypeDefKind::Variant(variant) => {
+                        self.src.push_str("variant<");
+                        for (i, case) in variant.cases.iter().enumerate() {
+                            if i > 0 {
+                                self.src.push_str(", ");
+                            }
+                            self.src.push_str(&case.name);
+                            match &case.ty {
+                                Type::Unit => {}
+                                ty => {
+                                    self.src.push_str("(");
+                                    self.print_ty(iface, ty, false);
+                                    self.src.push_str(")");
+                                }
+                            }
+                        }
+                        self.src.push_str(">");
                     }
                     TypeDefKind::List(Type::Char) => self.src.push_str("`string`"),
                     TypeDefKind::List(t) => {
@@ -528,33 +1077,50 @@ impl Markdown {
             Some(docs) => docs,
             None => return,
         };
+        // Wrap the rendered prose in placeholder markers so the final
+        // `resolve_hrefs` pass knows which backtick-quoted identifiers are
+        // doc-comment prose (safe to turn into links) as opposed to code
+        // generated elsewhere, like type signatures and headers. The owning
+        // interface name rides along so bare identifiers can still resolve
+        // against the interface-qualified `hrefs` keys merged output uses.
+        self.src.push_str(DOCS_START);
+        if let Some(iface) = &self.interface_name {
+            self.src.push_str(iface);
+        }
+        self.src.push_str(DOCS_IFACE_SEP);
         for line in docs.lines() {
             self.src.push_str("  ");
             self.src.push_str(line.trim());
             self.src.push_str("\n");
         }
+        self.src.push_str(DOCS_END);
     }
 
-    fn print_type_header(&mut self, name: &str) {
+    fn print_type_header(&mut self, name: &str, kind: &'static str) {
         if self.types == 0 {
             self.src.push_str("# Types\n\n");
         }
         self.types += 1;
+        let anchor_id = self.anchor_id(name);
         self.src.push_str(&format!(
-            "## <a href=\"#{}\" name=\"{0}\"></a> `{}`: ",
-            name.to_snake_case(),
-            name,
+            "## <a href=\"#{0}\" name=\"{0}\"></a> `{1}`: ",
+            anchor_id, name,
         ));
-        self.hrefs
-            .insert(name.to_string(), format!("#{}", name.to_snake_case()));
+        let href = format!("#{}", anchor_id);
+        self.toc_entries
+            .push((kind, self.toc_label(name), href.clone()));
+        self.hrefs.insert(self.href_key(name), href);
     }
 
-    fn print_type_info(&mut self, ty: TypeId, docs: &Docs) {
+    fn print_type_info(&mut self, iface: &Interface, ty: TypeId, docs: &Docs) {
         self.docs(docs);
         self.src.push_str("\n");
         self.src
             .push_str(&format!("Size: {}, ", self.sizes.size(&Type::Id(ty))));
         self.src
             .push_str(&format!("Alignment: {}\n", self.sizes.align(&Type::Id(ty))));
+        let lowered = flatten_type(iface, &Type::Id(ty));
+        self.src
+            .push_str(&format!("Lowered: {}\n", print_core_sig(&lowered)));
     }
 }